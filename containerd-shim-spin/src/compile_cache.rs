@@ -0,0 +1,228 @@
+//! A content-addressed on-disk cache for `SpinEngine::precompile` output.
+//!
+//! `precompile` recompiles every layer it is handed, even across shim
+//! process restarts and across the many apps on a node that share the same
+//! component layer. Keying the cache on `(layer digest, can_precompile
+//! hash)` means identical layers compiled under an identical engine
+//! configuration are skipped entirely on a cache hit, and any change to the
+//! engine config (see [`crate::wasmtime_config`]) naturally busts the cache
+//! because it changes the hash half of the key.
+
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use log::info;
+
+const SPIN_WASM_CACHE_DIR_ENV: &str = "SPIN_WASM_CACHE_DIR";
+const SPIN_WASM_CACHE_DISABLE_ENV: &str = "SPIN_WASM_CACHE_DISABLE";
+const SPIN_WASM_CACHE_MAX_BYTES_ENV: &str = "SPIN_WASM_CACHE_MAX_BYTES";
+
+const DEFAULT_CACHE_DIR: &str = "/var/lib/spin/compile-cache";
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024; // 10 GiB
+
+/// A content-addressed cache of precompiled component bytes, stored as one
+/// file per cache key under a configured directory.
+#[derive(Clone)]
+pub struct CompileCache {
+    dir: Option<PathBuf>,
+    max_bytes: u64,
+}
+
+impl CompileCache {
+    /// Builds a cache from env vars. Disabled entirely if
+    /// `SPIN_WASM_CACHE_DISABLE` is set.
+    pub fn from_env() -> Self {
+        if env::var(SPIN_WASM_CACHE_DISABLE_ENV).is_ok() {
+            return Self {
+                dir: None,
+                max_bytes: 0,
+            };
+        }
+
+        let dir = env::var(SPIN_WASM_CACHE_DIR_ENV).unwrap_or_else(|_| DEFAULT_CACHE_DIR.to_string());
+        let max_bytes = env::var(SPIN_WASM_CACHE_MAX_BYTES_ENV)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_BYTES);
+
+        Self {
+            dir: Some(PathBuf::from(dir)),
+            max_bytes,
+        }
+    }
+
+    /// The cache key for a layer with the given OCI digest, compiled under
+    /// the engine configuration summarized by `compatibility_hash` (the
+    /// value `SpinEngine::can_precompile` returns).
+    pub fn key(layer_digest: &str, compatibility_hash: &str) -> String {
+        // Both halves are already opaque, fixed-charset identifiers
+        // (a "sha256:..." digest and a hashed u64), so simple concatenation
+        // can't collide between the two parts.
+        format!("{layer_digest}-{compatibility_hash}")
+    }
+
+    fn path_for(&self, dir: &Path, key: &str) -> PathBuf {
+        dir.join(format!("{key}.cwasm"))
+    }
+
+    /// Returns the cached precompiled bytes for `key`, if present, bumping
+    /// its last-accessed time so it's less likely to be evicted.
+    ///
+    /// Callers must still validate the returned bytes (e.g. via
+    /// `Engine::detect_precompiled`) before trusting them: a cache entry
+    /// from an engine build that no longer matches isn't caught here, only
+    /// by the `compatibility_hash` half of the key staying in sync with the
+    /// engine config.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let dir = self.dir.as_ref()?;
+        let path = self.path_for(dir, key);
+        let bytes = fs::read(&path).ok()?;
+        // Best-effort LRU bookkeeping; a failure here shouldn't fail the hit.
+        let _ = filetime_touch(&path);
+        info!("compile cache hit for {key}");
+        Some(bytes)
+    }
+
+    /// Removes a cache entry, e.g. because its contents turned out to be
+    /// invalid (a truncated write from a prior crash, or a stale artifact
+    /// that slipped past the compatibility hash check).
+    pub fn remove(&self, key: &str) {
+        let Some(dir) = self.dir.as_ref() else {
+            return;
+        };
+        let _ = fs::remove_file(self.path_for(dir, key));
+    }
+
+    /// Stores `bytes` under `key`, then evicts the least-recently-used
+    /// entries until the cache is back under its size limit.
+    ///
+    /// Writes go to a temp file and are atomically renamed into place, so a
+    /// crash or a concurrent writer mid-write can never leave `get` serving
+    /// a truncated entry.
+    pub fn put(&self, key: &str, bytes: &[u8]) -> Result<()> {
+        let Some(dir) = self.dir.as_ref() else {
+            return Ok(());
+        };
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create compile cache directory {dir:?}"))?;
+
+        static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let tmp_path = dir.join(format!(
+            "{key}.cwasm.tmp-{}-{}",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp_path, bytes)
+            .with_context(|| format!("failed to write compile cache temp entry {tmp_path:?}"))?;
+
+        let path = self.path_for(dir, key);
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("failed to finalize compile cache entry {path:?}"))?;
+        // Stamp the new entry from the same clock `get` uses, so it ranks as
+        // more recently used than any entry `get` touched before it.
+        let _ = filetime_touch(&path);
+        self.evict_if_needed(dir)
+    }
+
+    fn evict_if_needed(&self, dir: &Path) -> Result<()> {
+        let mut entries: Vec<(PathBuf, SystemTime, u64)> = fs::read_dir(dir)
+            .with_context(|| format!("failed to list compile cache directory {dir:?}"))?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                // `get` bumps *mtime* via `filetime_touch`, not atime — sort
+                // on the field we actually touch. atime is frequently stale
+                // (disabled outright under `noatime`, coarsened under
+                // `relatime`), so ranking by it would evict hot entries as if
+                // they were cold.
+                let last_used = metadata.modified().ok()?;
+                Some((entry.path(), last_used, metadata.len()))
+            })
+            .collect();
+
+        let mut total: u64 = entries.iter().map(|(_, _, size)| size).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+
+        // Least-recently-used first.
+        entries.sort_by_key(|(_, last_used, _)| *last_used);
+        for (path, _, size) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total = total.saturating_sub(size);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bumps `path`'s modified time to now, so [`CompileCache::evict_if_needed`]
+/// ranks it as recently used.
+fn filetime_touch(path: &Path) -> std::io::Result<()> {
+    let now = SystemTime::now();
+    match fs::File::open(path) {
+        Ok(file) => file.set_modified(now),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_in(dir: &Path, max_bytes: u64) -> CompileCache {
+        CompileCache {
+            dir: Some(dir.to_path_buf()),
+            max_bytes,
+        }
+    }
+
+    #[test]
+    fn key_concatenates_digest_and_compatibility_hash() {
+        assert_eq!(
+            CompileCache::key("sha256:abc", "1234"),
+            "sha256:abc-1234"
+        );
+    }
+
+    #[test]
+    fn put_then_get_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache_in(dir.path(), u64::MAX);
+
+        cache.put("key-a", b"hello").unwrap();
+
+        assert_eq!(cache.get("key-a").as_deref(), Some(&b"hello"[..]));
+        assert_eq!(cache.get("missing"), None);
+    }
+
+    #[test]
+    fn evict_if_needed_keeps_the_most_recently_used_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        // Each entry is 5 bytes; only one fits under the limit at a time.
+        let cache = cache_in(dir.path(), 5);
+
+        cache.put("a", b"aaaaa").unwrap();
+        cache.put("b", b"bbbbb").unwrap();
+        // `b` was written after `a`, so without any further reads `a` is the
+        // least-recently-used entry and should have been evicted already.
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b").as_deref(), Some(&b"bbbbb"[..]));
+
+        // Re-`put`ting `a` makes it the most-recently-used entry again, so
+        // this eviction pass should take out `b` instead.
+        cache.put("a", b"aaaaa").unwrap();
+        assert_eq!(cache.get("a").as_deref(), Some(&b"aaaaa"[..]));
+        assert_eq!(cache.get("b"), None);
+    }
+}
@@ -1,7 +1,9 @@
 use std::{
     collections::{hash_map::DefaultHasher, HashSet},
     env,
+    future::Future,
     hash::{Hash, Hasher},
+    time::Duration,
 };
 
 use anyhow::{Context, Result};
@@ -16,24 +18,29 @@ use spin_app::locked::LockedApp;
 use spin_trigger::TriggerExecutor;
 use spin_trigger_http::HttpTrigger;
 use spin_trigger_redis::RedisTrigger;
-use tokio::runtime::Runtime;
+use tokio::{runtime::Runtime, task::JoinHandle, time};
 use trigger_command::CommandTrigger;
 use trigger_mqtt::MqttTrigger;
 use trigger_sqs::SqsTrigger;
 
 use crate::{
-    constants,
+    compile_cache, constants, epoch,
+    profiling::{self, ProfilingMode},
+    shutdown,
     source::Source,
+    telemetry,
     trigger::{build_trigger, get_supported_triggers},
     utils::{
         configure_application_variables_from_environment_variables, initialize_cache,
         is_wasm_content, parse_addr,
     },
+    wasmtime_config,
 };
 
 #[derive(Clone)]
 pub struct SpinEngine {
     pub(crate) wasmtime_engine: wasmtime::Engine,
+    pub(crate) compile_cache: compile_cache::CompileCache,
 }
 
 impl Default for SpinEngine {
@@ -42,8 +49,49 @@ impl Default for SpinEngine {
         // turned on for the components we compile.
         let mut config = wasmtime::Config::default();
         config.epoch_interruption(true);
+        wasmtime_config::apply(&mut config);
+
+        // Only consulted here, while building the engine: `perfmap`/`jitdump`
+        // bake their symbolization data into the compiled code itself, so
+        // once the engine is built there's nothing left for `SpinEngine` to
+        // hold onto or act on later.
+        let profiling_mode = ProfilingMode::from_env().unwrap_or_else(|err| {
+            log::warn!("ignoring invalid profiling configuration: {err:?}");
+            None
+        });
+        profiling::configure_engine(&mut config, profiling_mode);
+
         Self {
-            wasmtime_engine: wasmtime::Engine::new(&config).unwrap(),
+            wasmtime_engine: build_engine(config),
+            compile_cache: compile_cache::CompileCache::from_env(),
+        }
+    }
+}
+
+/// Builds a `wasmtime::Engine` from `config`, falling back to the on-demand
+/// allocator if construction fails.
+///
+/// `SPIN_WASM_POOLING_ALLOCATOR` lets an operator hand Wasmtime instance/
+/// memory limits that don't actually fit each other or the host (e.g.
+/// limits that overflow available address space) — `Engine::new` rejects
+/// those at construction
+/// time, and unwrapping that would crash the whole shim on bad operator
+/// input rather than just failing density tuning. Retrying once with the
+/// on-demand allocator keeps the shim running; if construction fails even
+/// without pooling, something more fundamental is wrong and there's nothing
+/// left to fall back to.
+fn build_engine(config: wasmtime::Config) -> wasmtime::Engine {
+    match wasmtime::Engine::new(&config) {
+        Ok(engine) => engine,
+        Err(err) => {
+            log::warn!(
+                "failed to build wasmtime engine with the configured allocation strategy, \
+                 falling back to the on-demand allocator: {err:?}"
+            );
+            let mut fallback = config;
+            fallback.allocation_strategy(wasmtime::InstanceAllocationStrategy::OnDemand);
+            wasmtime::Engine::new(&fallback)
+                .expect("failed to build wasmtime engine even with the on-demand allocator")
         }
     }
 }
@@ -58,22 +106,32 @@ impl Engine for SpinEngine {
         info!("setting up wasi");
         let rt = Runtime::new().context("failed to create runtime")?;
 
-        let (abortable, abort_handle) = futures::future::abortable(self.wasm_exec_async(ctx));
-        ctrlc::set_handler(move || abort_handle.abort())?;
+        let epoch_ticker = epoch::EpochTicker::spawn(
+            &rt,
+            self.wasmtime_engine.clone(),
+            epoch::epoch_interval_from_env(),
+        );
+
+        // On SIGINT/SIGTERM, stop and let triggers drain in-flight requests
+        // for a grace period (see `run_trigger`) instead of aborting
+        // everything mid-request. Listens cooperatively rather than via the
+        // process-global `ctrlc` handler so it doesn't shadow any signal
+        // handling the trigger executors do internally (see `shutdown`).
+        let (shutdown, shutdown_rx) = shutdown::ShutdownSignal::new();
+        shutdown::listen_for_os_signals(&rt, shutdown);
+
+        let result = rt.block_on(self.wasm_exec_async(ctx, shutdown_rx));
+        epoch_ticker.stop();
 
-        match rt.block_on(abortable) {
-            Ok(Ok(())) => {
+        match result {
+            Ok(()) => {
                 info!("run_wasi shut down: exiting");
                 Ok(0)
             }
-            Ok(Err(err)) => {
+            Err(err) => {
                 log::error!("run_wasi ERROR >>>  failed: {:?}", err);
                 Err(err)
             }
-            Err(aborted) => {
-                info!("Received signal to abort: {:?}", aborted);
-                Ok(0)
-            }
         }
     }
 
@@ -96,21 +154,34 @@ impl Engine for SpinEngine {
             .iter()
             .map(|layer| match is_wasm_content(layer) {
                 Some(wasm_layer) => {
-                    log::info!(
-                        "Precompile called for wasm layer {:?}",
-                        wasm_layer.config.digest()
-                    );
+                    let digest = wasm_layer.config.digest();
+                    log::info!("Precompile called for wasm layer {digest:?}");
                     if self
                         .wasmtime_engine
                         .detect_precompiled(&wasm_layer.layer)
                         .is_some()
                     {
-                        log::info!("Layer already precompiled {:?}", wasm_layer.config.digest());
+                        log::info!("Layer already precompiled {digest:?}");
                         Ok(Some(wasm_layer.layer))
                     } else {
+                        let compatibility_hash = self.can_precompile().unwrap_or_default();
+                        let cache_key = compile_cache::CompileCache::key(digest, &compatibility_hash);
+                        if let Some(cached) = self.compile_cache.get(&cache_key) {
+                            if self.wasmtime_engine.detect_precompiled(&cached).is_some() {
+                                return Ok(Some(cached));
+                            }
+                            log::warn!(
+                                "compile cache entry {cache_key} failed validation; discarding and recompiling"
+                            );
+                            self.compile_cache.remove(&cache_key);
+                        }
+
                         let component =
                             spin_componentize::componentize_if_necessary(&wasm_layer.layer)?;
                         let precompiled = self.wasmtime_engine.precompile_component(&component)?;
+                        if let Err(err) = self.compile_cache.put(&cache_key, &precompiled) {
+                            log::warn!("failed to write compile cache entry {cache_key}: {err:?}");
+                        }
                         Ok(Some(precompiled))
                     }
                 }
@@ -130,7 +201,11 @@ impl Engine for SpinEngine {
 }
 
 impl SpinEngine {
-    async fn wasm_exec_async(&self, ctx: &impl RuntimeContext) -> Result<()> {
+    async fn wasm_exec_async(
+        &self,
+        ctx: &impl RuntimeContext,
+        shutdown: shutdown::ShutdownReceiver,
+    ) -> Result<()> {
         let cache = initialize_cache().await?;
         let app_source = Source::from_ctx(ctx, &cache).await?;
         let locked_app = app_source.to_locked_app(&cache).await?;
@@ -138,19 +213,36 @@ impl SpinEngine {
         let trigger_cmds = get_supported_triggers(&locked_app)
             .with_context(|| format!("Couldn't find trigger executor for {app_source:?}"))?;
         let _telemetry_guard = spin_telemetry::init(version!().to_string())?;
+        telemetry::install_propagator();
 
-        self.run_trigger(ctx, &trigger_cmds, locked_app, app_source)
+        self.run_trigger(ctx, &trigger_cmds, locked_app, app_source, shutdown)
             .await
     }
 
+    /// Runs every trigger the app requires concurrently until either one of
+    /// them exits unexpectedly (an error, propagated with the offending
+    /// trigger's type) or `shutdown` fires.
+    ///
+    /// Each trigger is individually raced against `shutdown` (see
+    /// [`spawn_trigger_task`]): as soon as it fires, that trigger's `.run()`
+    /// future is given its own grace period to finish in-flight work before
+    /// being dropped, rather than every trigger running unchecked until one
+    /// shared deadline forces them all to abort at once.
+    ///
+    /// `CommandTrigger` is the one exception to "unexpected exit": it runs
+    /// the guest to completion and returning `Ok(())` is its normal,
+    /// expected exit, not a failure, so it's allowed to finish without
+    /// tearing down any other triggers still running alongside it.
     async fn run_trigger(
         &self,
         ctx: &impl RuntimeContext,
         trigger_types: &HashSet<String>,
         app: LockedApp,
         app_source: Source,
+        mut shutdown: shutdown::ShutdownReceiver,
     ) -> Result<()> {
-        let mut futures_list = Vec::new();
+        let grace_period = shutdown::grace_period_from_env();
+        let mut tasks = Vec::new();
         let mut trigger_type_map = Vec::new();
 
         for trigger_type in trigger_types.iter() {
@@ -201,23 +293,125 @@ impl SpinEngine {
             };
 
             trigger_type_map.push(trigger_type.clone());
-            futures_list.push(f);
+            tasks.push(spawn_trigger_task(
+                trigger_type.clone(),
+                f,
+                shutdown.clone(),
+                grace_period,
+            ));
         }
 
         info!(" >>> notifying main thread we are about to start");
 
-        // exit as soon as any of the trigger completes/exits
-        let (result, index, rest) = future::select_all(futures_list).await;
-        let trigger_type = &trigger_type_map[index];
-
-        info!(" >>> trigger type '{trigger_type}' exited");
+        let mut shutdown_requested = false;
 
-        drop(rest);
+        loop {
+            if tasks.is_empty() {
+                return Ok(());
+            }
 
-        result
+            tokio::select! {
+                (result, index, _) = future::select_all(tasks.iter_mut()) => {
+                    let trigger_type = trigger_type_map.remove(index);
+                    tasks.remove(index);
+                    match result {
+                        Ok(TriggerOutcome::TimedOut) => {
+                            log::warn!(
+                                " >>> trigger '{trigger_type}' still running after the shutdown grace period; stopped"
+                            );
+                        }
+                        // CommandTrigger runs the guest once and returns: `Ok(())`
+                        // is its normal success path, not an unexpected exit. The
+                        // other triggers run until shutdown, so `Ok(())` from any
+                        // of them before shutdown was requested means they died
+                        // without an error and still needs to tear down the rest
+                        // of the app; after shutdown it's the expected drain.
+                        Ok(TriggerOutcome::Completed(Ok(())))
+                            if shutdown_requested || trigger_type == CommandTrigger::TRIGGER_TYPE =>
+                        {
+                            info!(" >>> trigger '{trigger_type}' completed");
+                        }
+                        Ok(TriggerOutcome::Completed(Ok(()))) => {
+                            let err = anyhow::anyhow!("trigger '{trigger_type}' exited unexpectedly");
+                            log::error!(" >>> {err:?}");
+                            for task in &tasks {
+                                task.abort();
+                            }
+                            return Err(err);
+                        }
+                        Ok(TriggerOutcome::Completed(Err(err))) => {
+                            let err = err.context(format!("trigger '{trigger_type}' failed"));
+                            log::error!(" >>> {err:?}");
+                            for task in &tasks {
+                                task.abort();
+                            }
+                            return Err(err);
+                        }
+                        Err(join_err) => {
+                            let err = anyhow::Error::from(join_err)
+                                .context(format!("trigger '{trigger_type}' panicked"));
+                            log::error!(" >>> {err:?}");
+                            for task in &tasks {
+                                task.abort();
+                            }
+                            return Err(err);
+                        }
+                    }
+                }
+                _ = shutdown.triggered(), if !shutdown_requested => {
+                    shutdown_requested = true;
+                    info!(
+                        " >>> shutdown requested; each of the {} running trigger(s) has up to {grace_period:?} to finish before it is stopped",
+                        tasks.len()
+                    );
+                }
+            }
+        }
     }
 }
 
+/// The result of racing a single trigger's `.run()` future against
+/// `shutdown`.
+enum TriggerOutcome {
+    /// The trigger's `.run()` future resolved on its own, either before
+    /// shutdown was requested or within its grace period afterward.
+    Completed(Result<()>),
+    /// `shutdown` fired and the trigger's `.run()` future was still running
+    /// once its grace period elapsed, so it was dropped instead of awaited
+    /// further.
+    TimedOut,
+}
+
+/// Spawns `trigger`'s `.run()` future, handing it a clone of `shutdown` so
+/// it can stop on its own: racing the future against `shutdown` means this
+/// task (not just the orchestrating loop in `run_trigger`) reacts to
+/// shutdown immediately, giving `trigger` up to `grace_period` to finish
+/// in-flight work before its future is dropped — which, for e.g. an HTTP
+/// trigger, drops the listener and stops it from accepting new connections.
+fn spawn_trigger_task(
+    trigger_type: String,
+    f: impl Future<Output = Result<()>> + Send + 'static,
+    mut shutdown: shutdown::ShutdownReceiver,
+    grace_period: Duration,
+) -> JoinHandle<TriggerOutcome> {
+    tokio::spawn(async move {
+        tokio::pin!(f);
+        tokio::select! {
+            result = &mut f => TriggerOutcome::Completed(result),
+            _ = shutdown.triggered() => {
+                info!(
+                    " >>> trigger '{trigger_type}' stopping: shutdown requested, \
+                     draining in-flight work (grace period {grace_period:?})"
+                );
+                match time::timeout(grace_period, &mut f).await {
+                    Ok(result) => TriggerOutcome::Completed(result),
+                    Err(_) => TriggerOutcome::TimedOut,
+                }
+            }
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use oci_spec::image::MediaType;
@@ -226,6 +420,11 @@ mod tests {
 
     #[test]
     fn precompile() {
+        // `SpinEngine::default` builds a `CompileCache` from env, which
+        // defaults to writing under `/var/lib/spin/compile-cache`. Disable
+        // it so this test doesn't touch host state or depend on it.
+        std::env::set_var("SPIN_WASM_CACHE_DISABLE", "1");
+
         let module = wat::parse_str("(module)").unwrap();
         let wasmtime_engine = wasmtime::Engine::default();
         let component = wasmtime::component::Component::new(&wasmtime_engine, "(component)")
@@ -272,5 +471,7 @@ mod tests {
             component
         );
         assert!(precompiled[2].is_none());
+
+        std::env::remove_var("SPIN_WASM_CACHE_DISABLE");
     }
 }
@@ -0,0 +1,85 @@
+//! Drives `wasmtime::Engine::increment_epoch` on a fixed cadence.
+//!
+//! `SpinEngine` turns on `epoch_interruption`, but nothing advances the
+//! epoch unless something ticks it: without this, any per-invocation
+//! deadline Spin configures on a store can never fire and a runaway guest
+//! runs forever. [`EpochTicker`] is the thing that ticks it.
+//!
+//! KNOWN GAP: this module only ticks the epoch — it doesn't itself bound
+//! guest execution time. The ticker makes a store's epoch deadline fire *if*
+//! one was set, but setting that deadline (`Store::set_epoch_deadline`)
+//! happens at store-construction time, deep inside the `spin_trigger_*`
+//! executor crates this shim drives only through the opaque
+//! `TriggerExecutor::run` future. A shim-level `SPIN_MAX_WASM_EXECUTION_TIME_MS`
+//! knob would need those executors to read it when building a store, which
+//! this shim has no way to arrange; until that plumbing exists on the
+//! executor side, the requested hard execution-time bound isn't enforceable
+//! from here.
+
+use std::time::Duration;
+
+use tokio::{runtime::Runtime, task::JoinHandle, time};
+use wasmtime::Engine;
+
+const SPIN_WASM_EPOCH_INTERVAL_MS_ENV: &str = "SPIN_WASM_EPOCH_INTERVAL_MS";
+const DEFAULT_EPOCH_INTERVAL: Duration = Duration::from_millis(10);
+const MIN_EPOCH_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Reads the tick cadence from `SPIN_WASM_EPOCH_INTERVAL_MS`, defaulting to
+/// 10ms. Clamped to a minimum of 1ms: `tokio::time::interval` panics on a
+/// zero period, and a `0` here would otherwise silently kill the ticker task
+/// (and with it, every epoch deadline Spin sets) the moment it's spawned.
+pub fn epoch_interval_from_env() -> Duration {
+    std::env::var(SPIN_WASM_EPOCH_INTERVAL_MS_ENV)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_EPOCH_INTERVAL)
+        .max(MIN_EPOCH_INTERVAL)
+}
+
+/// A background task, spawned onto a `tokio::runtime::Runtime`, that ticks
+/// an engine's epoch at a fixed cadence. It runs until [`EpochTicker::stop`]
+/// is called or the owning `Runtime` is dropped, which cancels it.
+pub struct EpochTicker {
+    handle: JoinHandle<()>,
+}
+
+impl EpochTicker {
+    /// Spawns the ticker onto `rt`, incrementing `engine`'s epoch every
+    /// `interval`.
+    pub fn spawn(rt: &Runtime, engine: Engine, interval: Duration) -> Self {
+        let handle = rt.spawn(async move {
+            let mut ticker = time::interval(interval);
+            loop {
+                ticker.tick().await;
+                engine.increment_epoch();
+            }
+        });
+        Self { handle }
+    }
+
+    /// Stops the ticker by aborting its task on the `Runtime` it was spawned
+    /// on.
+    pub fn stop(self) {
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch_interval_from_env_clamps_zero() {
+        std::env::set_var(SPIN_WASM_EPOCH_INTERVAL_MS_ENV, "0");
+        assert_eq!(epoch_interval_from_env(), MIN_EPOCH_INTERVAL);
+        std::env::remove_var(SPIN_WASM_EPOCH_INTERVAL_MS_ENV);
+    }
+
+    #[test]
+    fn epoch_interval_from_env_defaults_when_unset() {
+        std::env::remove_var(SPIN_WASM_EPOCH_INTERVAL_MS_ENV);
+        assert_eq!(epoch_interval_from_env(), DEFAULT_EPOCH_INTERVAL);
+    }
+}
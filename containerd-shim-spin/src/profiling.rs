@@ -0,0 +1,65 @@
+//! Opt-in guest CPU profiling.
+//!
+//! Profiling only costs anything when an operator explicitly asks for it via
+//! `SPIN_WASM_PROFILE=perfmap|jitdump`: both backends just ask Wasmtime to
+//! bake symbolization data that Linux `perf`/jitdump tooling can read
+//! directly out of the compiled code, which this shim fully controls via
+//! `wasmtime::Config` before the engine is built.
+//!
+//! KNOWN GAP: the request this module was built for asked for a third,
+//! primary backend — sampling the guest call stack on every epoch tick via
+//! `wasmtime::GuestProfiler` and emitting a Firefox-profiler JSON file. That
+//! needs a `store.epoch_deadline_callback` on every store the guest runs in,
+//! and stores are constructed inside the `spin_trigger_*` executor crates
+//! this shim calls only through the opaque `TriggerExecutor::run` future —
+//! there's no callback hook exposed across that boundary. Shipping
+//! `SPIN_WASM_PROFILE=guest` as an accepted value with no working sampler
+//! behind it would be worse than not offering it, so it's simply treated as
+//! an unrecognized value rather than special-cased.
+
+use std::env;
+
+use anyhow::{bail, Result};
+use wasmtime::{Config, ProfilingStrategy};
+
+const SPIN_WASM_PROFILE_ENV: &str = "SPIN_WASM_PROFILE";
+
+/// Which profiling backend, if any, was requested via `SPIN_WASM_PROFILE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfilingMode {
+    /// Emit `perf`-symbolizable JIT frame information.
+    PerfMap,
+    /// Emit jitdump-format JIT frame information.
+    JitDump,
+}
+
+impl ProfilingMode {
+    /// Reads and validates `SPIN_WASM_PROFILE`. Returns `Ok(None)` if unset.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(value) = env::var(SPIN_WASM_PROFILE_ENV) else {
+            return Ok(None);
+        };
+        match value.as_str() {
+            "perfmap" => Ok(Some(Self::PerfMap)),
+            "jitdump" => Ok(Some(Self::JitDump)),
+            other => bail!(
+                "invalid {SPIN_WASM_PROFILE_ENV} value {other:?}: expected \"perfmap\" or \"jitdump\""
+            ),
+        }
+    }
+}
+
+/// Applies whichever profiling strategy was requested to the engine
+/// `Config`. Must run before the `wasmtime::Engine` is built: `perfmap` and
+/// `jitdump` bake symbolization metadata into the compiled code itself.
+pub fn configure_engine(config: &mut Config, mode: Option<ProfilingMode>) {
+    match mode {
+        Some(ProfilingMode::PerfMap) => {
+            config.profiler(ProfilingStrategy::PerfMap);
+        }
+        Some(ProfilingMode::JitDump) => {
+            config.profiler(ProfilingStrategy::JitDump);
+        }
+        None => {}
+    }
+}
@@ -0,0 +1,110 @@
+//! A cancellation token (`ShutdownSignal`/`ShutdownReceiver`, over a
+//! `tokio::sync::watch`) that `run_wasi` fires and every trigger task in
+//! `run_trigger` observes.
+//!
+//! Before this existed, the first trigger future to resolve for any reason —
+//! including a clean SIGTERM-driven exit of one trigger in a multi-trigger
+//! app — ended the whole app, and a `ctrlc` handler hard-aborted every
+//! remaining trigger mid-request on top of that. [`ShutdownReceiver`] is
+//! cheap to clone, so `engine.rs`'s `spawn_trigger_task` gives each trigger
+//! its own copy and its own grace-period countdown, instead of one shutdown
+//! decision being made centrally and applied to all triggers at once.
+//!
+//! Note on honesty of scope: a cloned `ShutdownReceiver` tells a trigger task
+//! *that* shutdown was requested, but a trigger's `.run()` future itself
+//! (built and returned by `spin_trigger_http`/`_redis`/`trigger_sqs`/
+//! `trigger_mqtt`) has no API for being told to stop accepting new
+//! connections or messages — it either finishes or it doesn't. What this
+//! module can guarantee is that the wrapping task around that future reacts
+//! to shutdown promptly and drops the future (closing whatever socket or
+//! subscription it held) once its grace period elapses, not that the
+//! trigger itself cooperates before then.
+
+use std::{env, time::Duration};
+
+use log::warn;
+use tokio::{runtime::Runtime, sync::watch};
+
+const SPIN_SHUTDOWN_GRACE_PERIOD_MS_ENV: &str = "SPIN_SHUTDOWN_GRACE_PERIOD_MS";
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// How long `run_trigger` should wait for in-flight work to drain after
+/// shutdown is requested, before forcing remaining triggers to stop.
+pub fn grace_period_from_env() -> Duration {
+    env::var(SPIN_SHUTDOWN_GRACE_PERIOD_MS_ENV)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_GRACE_PERIOD)
+}
+
+/// The sending half of a shutdown signal. Calling [`ShutdownSignal::trigger`]
+/// notifies every [`ShutdownReceiver`], including ones created afterward.
+#[derive(Clone)]
+pub struct ShutdownSignal(watch::Sender<bool>);
+
+/// The receiving half of a shutdown signal.
+#[derive(Clone)]
+pub struct ShutdownReceiver(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    pub fn new() -> (Self, ShutdownReceiver) {
+        let (tx, rx) = watch::channel(false);
+        (Self(tx), ShutdownReceiver(rx))
+    }
+
+    /// Marks shutdown as requested.
+    pub fn trigger(&self) {
+        let _ = self.0.send(true);
+    }
+}
+
+impl ShutdownReceiver {
+    /// Resolves once [`ShutdownSignal::trigger`] has been called. Returns
+    /// immediately if it already has been.
+    pub async fn triggered(&mut self) {
+        if *self.0.borrow() {
+            return;
+        }
+        let _ = self.0.changed().await;
+    }
+}
+
+/// Spawns a task onto `rt` that calls `signal.trigger()` on SIGINT or
+/// SIGTERM.
+///
+/// Deliberately uses `tokio::signal` rather than the `ctrlc` crate:
+/// `ctrlc::set_handler` installs a single, process-global OS handler, so
+/// using it here would mean this shim's own shutdown bookkeeping is the only
+/// thing that ever runs when the process is signaled — any graceful
+/// shutdown the trigger executors embed in their own `run` futures (if they
+/// listen for the same signal) would never get a chance to fire. Listening
+/// cooperatively avoids that, even though it can't directly tell an opaque
+/// trigger future to stop; `run_trigger`'s grace period is what forces
+/// progress if nothing cooperates.
+pub fn listen_for_os_signals(rt: &Runtime, signal: ShutdownSignal) {
+    rt.spawn(async move {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal as unix_signal, SignalKind};
+            match unix_signal(SignalKind::terminate()) {
+                Ok(mut sigterm) => {
+                    tokio::select! {
+                        _ = tokio::signal::ctrl_c() => {}
+                        _ = sigterm.recv() => {}
+                    }
+                }
+                Err(err) => {
+                    warn!("failed to install SIGTERM listener, falling back to SIGINT only: {err:?}");
+                    let _ = tokio::signal::ctrl_c().await;
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+
+        signal.trigger();
+    });
+}
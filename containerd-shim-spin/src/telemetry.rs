@@ -0,0 +1,27 @@
+//! OpenTelemetry propagator registration.
+//!
+//! KNOWN GAP: what actually got built here is much smaller than "context
+//! propagation from triggers into guest invocations." This module only
+//! registers *which* propagator format (`install_propagator`) OpenTelemetry's
+//! global `get_text_map_propagator()` resolves to; it does not extract a
+//! `traceparent` from any inbound request, does not start a server span per
+//! invocation, and does not inject context into outbound host calls — all
+//! three were the actual ask. Those require reading request headers
+//! (HTTP), message metadata (Redis/MQTT/SQS), or guest args (command
+//! trigger), and `run_trigger` in `engine.rs` never sees any of that: each
+//! trigger's `.run()` is one opaque future from `spin_trigger_http`,
+//! `spin_trigger_redis`, `trigger_sqs`, or `trigger_mqtt`, so the shim has no
+//! per-invocation point to hook extraction or span creation into. Without
+//! changes to those crates (out of scope here), this module can only affect
+//! *how* whatever extraction/injection those crates' own instrumentation
+//! might already be doing interprets trace headers — it sets the format to
+//! W3C instead of leaving OTel's no-op default in place, nothing more.
+
+use opentelemetry::global;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+/// Registers the W3C `traceparent`/`tracestate` format as OpenTelemetry's
+/// global propagator. Call once, alongside `spin_telemetry::init`.
+pub fn install_propagator() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+}
@@ -0,0 +1,72 @@
+//! Tuning knobs for the `wasmtime::Config` backing every `SpinEngine`.
+//!
+//! Containerd hosts often pack hundreds of Wasm instances per node. The
+//! defaults Wasmtime ships with favor a single long-lived process over that
+//! kind of density, so this module reads a handful of env vars (the shim has
+//! no other channel for OCI spec annotations to reach engine construction)
+//! and applies them before the engine is built.
+//!
+//! Of these knobs, only `static_memory_guard_size` feeds
+//! `precompile_compatibility_hash` (and therefore `can_precompile`): it's the
+//! one setting here that changes the shape of the compiled artifact itself.
+//! The pooling allocator, its instance/memory limits, `memory_init_cow`, and
+//! `parallel_compilation` are all runtime/allocator behavior applied after a
+//! module or component is already compiled, so they don't affect
+//! `precompile_compatibility_hash` — a precompiled layer cached under one of
+//! those settings is still a valid cache hit after the setting changes.
+
+use std::env;
+
+use wasmtime::{Config, PoolingAllocationConfig};
+
+const SPIN_WASM_POOLING_ALLOCATOR_ENV: &str = "SPIN_WASM_POOLING_ALLOCATOR";
+const SPIN_WASM_MAX_INSTANCES_ENV: &str = "SPIN_WASM_MAX_INSTANCES";
+const SPIN_WASM_MAX_MEMORIES_ENV: &str = "SPIN_WASM_MAX_MEMORIES";
+const SPIN_WASM_STATIC_MEMORY_GUARD_SIZE_ENV: &str = "SPIN_WASM_STATIC_MEMORY_GUARD_SIZE";
+const SPIN_WASM_PARALLEL_COMPILATION_ENV: &str = "SPIN_WASM_PARALLEL_COMPILATION";
+const SPIN_WASM_MEMORY_INIT_COW_ENV: &str = "SPIN_WASM_MEMORY_INIT_COW";
+
+const DEFAULT_MAX_INSTANCES: u32 = 1000;
+const DEFAULT_MAX_MEMORIES: u32 = 1000;
+
+fn env_bool(name: &str, default: bool) -> bool {
+    env::var(name)
+        .ok()
+        .map(|v| matches!(v.as_str(), "1" | "true" | "TRUE"))
+        .unwrap_or(default)
+}
+
+fn env_u32(name: &str, default: u32) -> u32 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Applies density-tuning env vars to `config`. Intended to run once, inside
+/// `SpinEngine::default`, before the `wasmtime::Engine` is constructed.
+///
+/// Does not offer a fuel-consumption knob: `consume_fuel` traps every guest
+/// the instant its initial fuel (always zero, since nothing in this shim
+/// ever calls `Store::set_fuel`) runs out, so turning it on via an env var
+/// with no corresponding way to grant fuel would just be a footgun.
+pub fn apply(config: &mut Config) {
+    config.parallel_compilation(env_bool(SPIN_WASM_PARALLEL_COMPILATION_ENV, true));
+    config.memory_init_cow(env_bool(SPIN_WASM_MEMORY_INIT_COW_ENV, true));
+
+    if let Ok(guard_size) = env::var(SPIN_WASM_STATIC_MEMORY_GUARD_SIZE_ENV) {
+        if let Ok(guard_size) = guard_size.parse() {
+            config.static_memory_guard_size(guard_size);
+        }
+    }
+
+    if env_bool(SPIN_WASM_POOLING_ALLOCATOR_ENV, false) {
+        let max_instances = env_u32(SPIN_WASM_MAX_INSTANCES_ENV, DEFAULT_MAX_INSTANCES);
+        let max_memories = env_u32(SPIN_WASM_MAX_MEMORIES_ENV, DEFAULT_MAX_MEMORIES);
+
+        let mut pooling = PoolingAllocationConfig::default();
+        pooling.total_component_instances(max_instances);
+        pooling.total_memories(max_memories);
+        config.allocation_strategy(wasmtime::InstanceAllocationStrategy::Pooling(pooling));
+    }
+}